@@ -1,6 +1,10 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod migrations;
+mod query;
+mod search;
+
 use std::fs;
 use std::sync::Mutex;
 use rusqlite::Connection;
@@ -95,20 +99,77 @@ fn select_world_file() -> Result<Option<String>, String> {
     }
 }
 
-#[tauri::command]
-fn execute_sql(
-    db_path: String,
-    query: String,
-    values: Vec<serde_json::Value>,
-) -> Result<QueryResult, String> {
-    let conn = Connection::open(&db_path)
+// Opens a fresh connection to `db_path` and applies the pragmas every
+// connection in the pool must have before it's handed out.
+fn open_connection(db_path: &str) -> Result<Connection, String> {
+    open_connection_keyed(db_path, None)
+}
+
+// Same as `open_connection`, but when a `passphrase` is given and the
+// `bundled-sqlcipher` backend is enabled, keys the connection first. The
+// `PRAGMA key` must run before any other statement touches the database.
+fn open_connection_keyed(db_path: &str, passphrase: Option<&str>) -> Result<Connection, String> {
+    let conn = Connection::open(db_path)
         .map_err(|e| format!("Failed to open database: {}", e))?;
 
-    // Convert JSON values to rusqlite values
-    let params: Vec<Box<dyn rusqlite::ToSql>> = values
+    #[cfg(feature = "bundled-sqlcipher")]
+    if let Some(passphrase) = passphrase {
+        conn.pragma_update(None, "key", passphrase)
+            .map_err(|e| format!("Failed to key database: {}", e))?;
+
+        // `PRAGMA key` itself never fails on a wrong passphrase -- SQLCipher
+        // only notices once it tries to actually read the file. Probe with a
+        // cheap query *before* the WAL/synchronous pragmas below, which would
+        // otherwise surface the same failure as an opaque "file is not a
+        // database" config error instead of "Incorrect passphrase".
+        conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map_err(|_| "Incorrect passphrase".to_string())?;
+    }
+    #[cfg(not(feature = "bundled-sqlcipher"))]
+    let _ = passphrase;
+
+    conn.execute_batch(
+        "PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL; PRAGMA foreign_keys=ON;",
+    )
+    .map_err(|e| format!("Failed to configure database: {}", e))?;
+
+    Ok(conn)
+}
+
+// A BLOB round-trips through JSON as `{"$blob": "<base64>"}` since JSON has
+// no native binary type. Returns `Ok(None)` for an object that isn't a
+// `$blob` wrapper at all, and `Err` for one that is but is malformed --
+// callers must not fall back to stringifying a malformed blob, since that
+// would silently bind the literal JSON text instead of the intended bytes.
+fn blob_as_base64(
+    object: &serde_json::Map<String, serde_json::Value>,
+) -> Result<Option<Vec<u8>>, String> {
+    use base64::Engine;
+
+    let Some(value) = object.get("$blob") else {
+        return Ok(None);
+    };
+
+    let encoded = value
+        .as_str()
+        .ok_or_else(|| "\"$blob\" value must be a base64 string".to_string())?;
+
+    base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map(Some)
+        .map_err(|e| format!("Invalid base64 in \"$blob\" value: {}", e))
+}
+
+// Convert JSON values to rusqlite values
+fn json_values_to_params(
+    values: &[serde_json::Value],
+) -> Result<Vec<Box<dyn rusqlite::ToSql>>, String> {
+    values
         .iter()
-        .map(|v| -> Box<dyn rusqlite::ToSql> {
-            match v {
+        .map(|v| -> Result<Box<dyn rusqlite::ToSql>, String> {
+            Ok(match v {
                 serde_json::Value::String(s) => Box::new(s.clone()),
                 serde_json::Value::Number(n) => {
                     if let Some(i) = n.as_i64() {
@@ -121,14 +182,29 @@ fn execute_sql(
                 }
                 serde_json::Value::Bool(b) => Box::new(*b),
                 serde_json::Value::Null => Box::new(rusqlite::types::Null),
+                serde_json::Value::Object(object) => match blob_as_base64(object)? {
+                    Some(bytes) => Box::new(bytes),
+                    None => Box::new(v.to_string()),
+                },
                 _ => Box::new(v.to_string()),
-            }
+            })
         })
-        .collect();
+        .collect()
+}
 
+// Runs a single query or statement against `conn`, returning rows for
+// SELECTs or affected-row/last-insert-id bookkeeping otherwise. Shared by
+// `execute_sql` and `execute_batch` so both go through the same JSON<->SQL
+// conversion and SELECT-detection logic.
+fn run_statement(
+    conn: &Connection,
+    query: &str,
+    values: &[serde_json::Value],
+) -> Result<QueryResult, String> {
+    let params = json_values_to_params(values)?;
     let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
 
-    let mut stmt = conn.prepare(&query)
+    let mut stmt = conn.prepare(query)
         .map_err(|e| format!("Failed to prepare statement: {}", e))?;
 
     // Check if this is a SELECT query
@@ -149,7 +225,12 @@ fn execute_sql(
                         rusqlite::types::Value::Integer(i) => serde_json::json!(i),
                         rusqlite::types::Value::Real(f) => serde_json::json!(f),
                         rusqlite::types::Value::Text(s) => serde_json::Value::String(s),
-                        rusqlite::types::Value::Blob(_) => serde_json::Value::Null,
+                        rusqlite::types::Value::Blob(bytes) => {
+                            use base64::Engine;
+                            serde_json::json!({
+                                "$blob": base64::engine::general_purpose::STANDARD.encode(bytes)
+                            })
+                        }
                     };
                     map.insert(name.clone(), json_value);
                 }
@@ -177,6 +258,210 @@ fn execute_sql(
     }
 }
 
+#[tauri::command]
+fn execute_sql(
+    db_state: tauri::State<'_, DbState>,
+    db_path: String,
+    query: String,
+    values: Vec<serde_json::Value>,
+) -> Result<QueryResult, String> {
+    let mut connections = db_state
+        .connections
+        .lock()
+        .map_err(|e| format!("Failed to lock connection pool: {}", e))?;
+
+    if !connections.contains_key(&db_path) {
+        let conn = open_connection(&db_path)?;
+        connections.insert(db_path.clone(), conn);
+    }
+
+    let conn = connections
+        .get(&db_path)
+        .ok_or_else(|| "Failed to retrieve pooled connection".to_string())?;
+
+    run_statement(conn, &query, &values)
+}
+
+#[derive(Deserialize)]
+struct BatchStatement {
+    sql: String,
+    values: Vec<serde_json::Value>,
+}
+
+#[tauri::command]
+fn execute_batch(
+    db_state: tauri::State<'_, DbState>,
+    db_path: String,
+    statements: Vec<BatchStatement>,
+) -> Result<Vec<QueryResult>, String> {
+    let mut connections = db_state
+        .connections
+        .lock()
+        .map_err(|e| format!("Failed to lock connection pool: {}", e))?;
+
+    if !connections.contains_key(&db_path) {
+        let conn = open_connection(&db_path)?;
+        connections.insert(db_path.clone(), conn);
+    }
+
+    let conn = connections
+        .get_mut(&db_path)
+        .ok_or_else(|| "Failed to retrieve pooled connection".to_string())?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    let mut results = Vec::with_capacity(statements.len());
+    for (index, statement) in statements.iter().enumerate() {
+        match run_statement(&tx, &statement.sql, &statement.values) {
+            Ok(result) => results.push(result),
+            Err(e) => return Err(format!("Statement {} failed: {}", index, e)),
+        }
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+    Ok(results)
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiaryEntry {
+    id: i64,
+    title: String,
+    body: String,
+    created_at: String,
+    updated_at: String,
+}
+
+#[tauri::command]
+fn get_entries(
+    db_state: tauri::State<'_, DbState>,
+    db_path: String,
+) -> Result<Vec<DiaryEntry>, String> {
+    let mut connections = db_state
+        .connections
+        .lock()
+        .map_err(|e| format!("Failed to lock connection pool: {}", e))?;
+
+    if !connections.contains_key(&db_path) {
+        let conn = open_connection(&db_path)?;
+        connections.insert(db_path.clone(), conn);
+    }
+
+    let conn = connections
+        .get(&db_path)
+        .ok_or_else(|| "Failed to retrieve pooled connection".to_string())?;
+
+    query::query_as::<DiaryEntry>(
+        conn,
+        "SELECT id, title, body, created_at, updated_at FROM diary_entries ORDER BY created_at DESC",
+        &[],
+    )
+}
+
+#[cfg(feature = "bundled-sqlcipher")]
+#[tauri::command]
+fn unlock_database(
+    db_state: tauri::State<'_, DbState>,
+    db_path: String,
+    passphrase: String,
+) -> Result<bool, String> {
+    let mut conn = open_connection_keyed(&db_path, Some(&passphrase))?;
+
+    // An encrypted database can't be migrated until it's keyed, so startup
+    // skips its automatic `migrate` call for this build and it happens here
+    // instead, once the passphrase has been validated.
+    migrations::migrate(&mut conn)?;
+
+    let mut connections = db_state
+        .connections
+        .lock()
+        .map_err(|e| format!("Failed to lock connection pool: {}", e))?;
+    connections.insert(db_path, conn);
+
+    Ok(true)
+}
+
+// Without `bundled-sqlcipher` there's no encryption to unlock, so the
+// command must say so rather than silently accepting any passphrase.
+#[cfg(not(feature = "bundled-sqlcipher"))]
+#[tauri::command]
+fn unlock_database(
+    _db_state: tauri::State<'_, DbState>,
+    _db_path: String,
+    _passphrase: String,
+) -> Result<bool, String> {
+    Err("This build does not support encrypted databases (bundled-sqlcipher feature is disabled)".to_string())
+}
+
+#[cfg(feature = "bundled-sqlcipher")]
+#[tauri::command]
+fn rekey_database(
+    db_state: tauri::State<'_, DbState>,
+    db_path: String,
+    old_passphrase: String,
+    new_passphrase: String,
+) -> Result<(), String> {
+    let conn = open_connection_keyed(&db_path, Some(&old_passphrase))?;
+
+    conn.pragma_update(None, "rekey", &new_passphrase)
+        .map_err(|e| format!("Failed to rekey database: {}", e))?;
+
+    // `conn` is now keyed with `new_passphrase`; replace whatever was
+    // cached under the old key so `execute_sql` can't reuse a
+    // stale-keyed connection.
+    let mut connections = db_state
+        .connections
+        .lock()
+        .map_err(|e| format!("Failed to lock connection pool: {}", e))?;
+    connections.insert(db_path, conn);
+
+    Ok(())
+}
+
+#[cfg(not(feature = "bundled-sqlcipher"))]
+#[tauri::command]
+fn rekey_database(
+    _db_state: tauri::State<'_, DbState>,
+    _db_path: String,
+    _old_passphrase: String,
+    _new_passphrase: String,
+) -> Result<(), String> {
+    Err("This build does not support encrypted databases (bundled-sqlcipher feature is disabled)".to_string())
+}
+
+#[tauri::command]
+fn migrate(db_path: String) -> Result<usize, String> {
+    let mut conn = open_connection(&db_path)?;
+    migrations::migrate(&mut conn)
+}
+
+#[tauri::command]
+fn search_entries(
+    db_state: tauri::State<'_, DbState>,
+    db_path: String,
+    query: String,
+    limit: usize,
+) -> Result<Vec<search::SearchHit>, String> {
+    let mut connections = db_state
+        .connections
+        .lock()
+        .map_err(|e| format!("Failed to lock connection pool: {}", e))?;
+
+    if !connections.contains_key(&db_path) {
+        let conn = open_connection(&db_path)?;
+        connections.insert(db_path.clone(), conn);
+    }
+
+    let conn = connections
+        .get(&db_path)
+        .ok_or_else(|| "Failed to retrieve pooled connection".to_string())?;
+
+    search::search_entries(conn, &query, limit)
+}
+
 use tauri::{CustomMenuItem, SystemTray, SystemTrayMenu, SystemTrayMenuItem, SystemTrayEvent, Manager};
 
 fn main() {
@@ -189,6 +474,26 @@ fn main() {
     let system_tray = SystemTray::new().with_menu(tray_menu);
 
     tauri::Builder::default()
+        .setup(|app| {
+            let app_handle = app.handle();
+            let db_path = get_database_path(app_handle)?;
+
+            // In a `bundled-sqlcipher` build the database may be encrypted,
+            // so it can't be opened -- let alone migrated -- until the user
+            // supplies a passphrase through `unlock_database`, which runs
+            // migrations itself once the connection is successfully keyed.
+            #[cfg(not(feature = "bundled-sqlcipher"))]
+            {
+                let applied = migrate(db_path)?;
+                if applied > 0 {
+                    println!("Applied {} database migration(s)", applied);
+                }
+            }
+            #[cfg(feature = "bundled-sqlcipher")]
+            let _ = db_path;
+
+            Ok(())
+        })
         .system_tray(system_tray)
         .on_system_tray_event(|app, event| match event {
             SystemTrayEvent::LeftClick {
@@ -219,6 +524,9 @@ fn main() {
             }
             _ => {}
         })
+        .manage(DbState {
+            connections: Mutex::new(HashMap::new()),
+        })
         .invoke_handler(tauri::generate_handler![
             get_app_data_dir,
             read_world_settings,
@@ -226,7 +534,43 @@ fn main() {
             get_database_path,
             select_world_file,
             execute_sql,
+            execute_batch,
+            migrate,
+            search_entries,
+            unlock_database,
+            rekey_database,
+            get_entries,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blob_round_trips_through_base64() {
+        use base64::Engine;
+
+        let bytes = vec![0u8, 1, 2, 255, 254];
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
+        let object = serde_json::json!({ "$blob": encoded });
+
+        let decoded = blob_as_base64(object.as_object().unwrap()).unwrap();
+        assert_eq!(decoded, Some(bytes));
+    }
+
+    #[test]
+    fn non_blob_object_is_not_a_blob() {
+        let object = serde_json::json!({ "foo": "bar" });
+        let decoded = blob_as_base64(object.as_object().unwrap()).unwrap();
+        assert_eq!(decoded, None);
+    }
+
+    #[test]
+    fn malformed_blob_is_an_error() {
+        let object = serde_json::json!({ "$blob": "not valid base64!!" });
+        assert!(blob_as_base64(object.as_object().unwrap()).is_err());
+    }
+}
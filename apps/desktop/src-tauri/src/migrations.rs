@@ -0,0 +1,157 @@
+// Versioned schema migrations, modeled after the `rusqlite_migration` crate:
+// each step is plain up-SQL (optionally paired with down-SQL), and the
+// database's progress through the list is tracked in `PRAGMA user_version`.
+
+use rusqlite::Connection;
+
+struct Migration {
+    up: &'static str,
+    #[allow(dead_code)]
+    down: Option<&'static str>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        // `IF NOT EXISTS`: real installs already have this table, since it's
+        // exactly what the frontend used to `CREATE TABLE` by hand through
+        // `execute_sql` before this migration system existed. Those files
+        // still read `user_version = 0`, so this step must tolerate running
+        // against a database that already has the table rather than failing
+        // startup with "table diary_entries already exists".
+        up: "CREATE TABLE IF NOT EXISTS diary_entries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );",
+        down: Some("DROP TABLE diary_entries;"),
+    },
+    Migration {
+        up: "CREATE TABLE tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE entry_tags (
+            entry_id INTEGER NOT NULL REFERENCES diary_entries(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (entry_id, tag_id)
+        );",
+        down: Some("DROP TABLE entry_tags; DROP TABLE tags;"),
+    },
+    Migration {
+        up: "CREATE TABLE quest_progress (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entry_id INTEGER NOT NULL REFERENCES diary_entries(id) ON DELETE CASCADE,
+            quest_name TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'in_progress'
+        );",
+        down: Some("DROP TABLE quest_progress;"),
+    },
+    Migration {
+        // The `trigram` tokenizer lets CJK queries match on substrings
+        // rather than whitespace-delimited words, which matters for the
+        // Japanese UI this app ships with.
+        up: "CREATE VIRTUAL TABLE diary_entries_fts USING fts5(
+            title, body,
+            content='diary_entries', content_rowid='id',
+            tokenize='trigram'
+        );
+        INSERT INTO diary_entries_fts(rowid, title, body)
+            SELECT id, title, body FROM diary_entries;
+        CREATE TRIGGER diary_entries_fts_ai AFTER INSERT ON diary_entries BEGIN
+            INSERT INTO diary_entries_fts(rowid, title, body) VALUES (new.id, new.title, new.body);
+        END;
+        CREATE TRIGGER diary_entries_fts_ad AFTER DELETE ON diary_entries BEGIN
+            INSERT INTO diary_entries_fts(diary_entries_fts, rowid, title, body)
+                VALUES ('delete', old.id, old.title, old.body);
+        END;
+        CREATE TRIGGER diary_entries_fts_au AFTER UPDATE ON diary_entries BEGIN
+            INSERT INTO diary_entries_fts(diary_entries_fts, rowid, title, body)
+                VALUES ('delete', old.id, old.title, old.body);
+            INSERT INTO diary_entries_fts(rowid, title, body) VALUES (new.id, new.title, new.body);
+        END;",
+        down: Some(
+            "DROP TRIGGER diary_entries_fts_au;
+             DROP TRIGGER diary_entries_fts_ad;
+             DROP TRIGGER diary_entries_fts_ai;
+             DROP TABLE diary_entries_fts;",
+        ),
+    },
+];
+
+fn user_version(conn: &Connection) -> Result<usize, String> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))
+        .map(|v| v as usize)
+        .map_err(|e| format!("Failed to read schema version: {}", e))
+}
+
+// Applies every migration whose index is greater than the database's
+// current `user_version`, inside a single transaction, and returns how
+// many steps were applied. The `user_version` bump happens on the same
+// transaction as the migrations themselves, so a crash between them can't
+// leave the schema created but the version still at 0 (which would make
+// the next `migrate` call re-run `CREATE TABLE` and fail permanently).
+pub fn migrate(conn: &mut Connection) -> Result<usize, String> {
+    let current_version = user_version(conn)?;
+
+    if current_version >= MIGRATIONS.len() {
+        return Ok(0);
+    }
+
+    let pending = &MIGRATIONS[current_version..];
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start migration transaction: {}", e))?;
+
+    for migration in pending {
+        tx.execute_batch(migration.up)
+            .map_err(|e| format!("Failed to apply migration: {}", e))?;
+    }
+
+    tx.execute_batch(&format!("PRAGMA user_version = {};", MIGRATIONS.len()))
+        .map_err(|e| format!("Failed to update schema version: {}", e))?;
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit migrations: {}", e))?;
+
+    Ok(pending.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_is_idempotent() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let applied = migrate(&mut conn).unwrap();
+        assert_eq!(applied, MIGRATIONS.len());
+
+        let applied_again = migrate(&mut conn).unwrap();
+        assert_eq!(applied_again, 0);
+    }
+
+    #[test]
+    fn migrate_reconciles_a_pre_existing_legacy_table() {
+        // Simulates a real install from before this migration system
+        // existed: the frontend already fired a raw `CREATE TABLE`, so the
+        // table exists but `user_version` is still 0.
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE diary_entries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title TEXT NOT NULL,
+                body TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+        )
+        .unwrap();
+
+        let applied = migrate(&mut conn).unwrap();
+        assert_eq!(applied, MIGRATIONS.len());
+    }
+}
@@ -0,0 +1,115 @@
+// Typed row deserialization, modeled after `serde_rusqlite`: rows are read
+// into a column-name map and then deserialized into a caller-supplied type,
+// instead of callers picking fields back out of a `HashMap` by hand.
+
+use rusqlite::Connection;
+use serde::de::DeserializeOwned;
+
+fn row_to_json(
+    row: &rusqlite::Row,
+    column_names: &[String],
+) -> rusqlite::Result<serde_json::Value> {
+    let mut map = serde_json::Map::with_capacity(column_names.len());
+    for (i, name) in column_names.iter().enumerate() {
+        let value: rusqlite::types::Value = row.get(i)?;
+        let json_value = match value {
+            rusqlite::types::Value::Null => serde_json::Value::Null,
+            rusqlite::types::Value::Integer(i) => serde_json::json!(i),
+            rusqlite::types::Value::Real(f) => serde_json::json!(f),
+            rusqlite::types::Value::Text(s) => serde_json::Value::String(s),
+            rusqlite::types::Value::Blob(bytes) => {
+                use base64::Engine;
+                serde_json::json!({ "$blob": base64::engine::general_purpose::STANDARD.encode(bytes) })
+            }
+        };
+        map.insert(name.clone(), json_value);
+    }
+    Ok(serde_json::Value::Object(map))
+}
+
+// Runs `sql` and deserializes each row into `T` by column name, catching
+// schema/field mismatches as a `Result` here instead of at runtime in JS.
+pub fn query_as<T: DeserializeOwned>(
+    conn: &Connection,
+    sql: &str,
+    params: &[&dyn rusqlite::ToSql],
+) -> Result<Vec<T>, String> {
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+
+    let column_count = stmt.column_count();
+    let column_names: Vec<String> = (0..column_count)
+        .map(|i| stmt.column_name(i).unwrap_or("").to_string())
+        .collect();
+
+    let rows = stmt
+        .query_map(params, |row| row_to_json(row, &column_names))
+        .map_err(|e| format!("Query failed: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to collect rows: {}", e))?;
+
+    rows.into_iter()
+        .map(|row| serde_json::from_value(row).map_err(|e| format!("Failed to deserialize row: {}", e)))
+        .collect()
+}
+
+// A row that can be built directly from positional columns, bypassing the
+// column-name/JSON round trip `query_as` does. Implemented below for tuples
+// of up to four `FromSql` columns, for callers that just want
+// `(i64, String)`-shaped results without declaring a struct.
+pub trait FromRow: Sized {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self>;
+}
+
+macro_rules! impl_from_row_for_tuple {
+    ($($idx:tt : $T:ident),+) => {
+        impl<$($T: rusqlite::types::FromSql),+> FromRow for ($($T,)+) {
+            fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+                Ok(($(row.get($idx)?,)+))
+            }
+        }
+    };
+}
+
+impl_from_row_for_tuple!(0: A);
+impl_from_row_for_tuple!(0: A, 1: B);
+impl_from_row_for_tuple!(0: A, 1: B, 2: C);
+impl_from_row_for_tuple!(0: A, 1: B, 2: C, 3: D);
+
+// Runs `sql` and maps each row straight into `T` via `FromRow`, for tuple
+// results where a full struct + `query_as` would be overkill.
+pub fn query_as_tuples<T: FromRow>(
+    conn: &Connection,
+    sql: &str,
+    params: &[&dyn rusqlite::ToSql],
+) -> Result<Vec<T>, String> {
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+
+    stmt.query_map(params, |row| T::from_row(row))
+        .map_err(|e| format!("Query failed: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to collect rows: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_as_tuples_maps_positional_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (id INTEGER, name TEXT);
+             INSERT INTO t VALUES (1, 'alice'), (2, 'bob');",
+        )
+        .unwrap();
+
+        let rows: Vec<(i64, String)> =
+            query_as_tuples(&conn, "SELECT id, name FROM t ORDER BY id", &[]).unwrap();
+
+        assert_eq!(rows, vec![(1, "alice".to_string()), (2, "bob".to_string())]);
+    }
+}
@@ -0,0 +1,44 @@
+// Full-text search over diary entries, backed by the `diary_entries_fts`
+// FTS5 virtual table created in `migrations`.
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct SearchHit {
+    #[serde(rename = "entryId")]
+    pub entry_id: i64,
+    pub rank: f64,
+    pub snippet: String,
+}
+
+pub fn search_entries(
+    conn: &Connection,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchHit>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT rowid, bm25(diary_entries_fts) AS rank,
+                    snippet(diary_entries_fts, -1, '<mark>', '</mark>', '...', 8) AS snippet
+             FROM diary_entries_fts
+             WHERE diary_entries_fts MATCH ?1
+             ORDER BY rank
+             LIMIT ?2",
+        )
+        .map_err(|e| format!("Failed to prepare search query: {}", e))?;
+
+    let hits = stmt
+        .query_map(rusqlite::params![query, limit as i64], |row| {
+            Ok(SearchHit {
+                entry_id: row.get(0)?,
+                rank: row.get(1)?,
+                snippet: row.get(2)?,
+            })
+        })
+        .map_err(|e| format!("Search query failed: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to collect search hits: {}", e))?;
+
+    Ok(hits)
+}